@@ -0,0 +1,279 @@
+// Copyright (C) 2019-2021 Aleo Systems Inc.
+// This file is part of the snarkOS library.
+
+// The snarkOS library is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// The snarkOS library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with the snarkOS library. If not, see <https://www.gnu.org/licenses/>.
+
+//! A view-based BFT finality gadget that runs alongside the longest-chain fork choice rule.
+//! Validators vote on canon blocks in two phases (`Prevote` then `Precommit`); once a height
+//! collects precommits from more than two-thirds of validator stake it is recorded as
+//! finalized and `scan_forks`/commit may no longer consider forks below it.
+
+use std::collections::{BTreeMap, HashMap};
+
+use snarkos_storage::Digest;
+
+/// A validator's signature over a `(height, block_hash)` pair, identified by its public key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidatorSignature {
+    pub validator: ValidatorId,
+    pub signature: Vec<u8>,
+}
+
+/// Opaque validator identity; stake lookups are keyed by this.
+pub type ValidatorId = Vec<u8>;
+
+/// A first-round vote for `(height, block_hash)` at the gadget's current view.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Prevote {
+    pub view: u64,
+    pub height: u32,
+    pub block_hash: Digest,
+    pub vote: ValidatorSignature,
+}
+
+/// A second-round vote, cast once a validator has observed a quorum of matching prevotes.
+/// A quorum of precommits finalizes `(height, block_hash)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Precommit {
+    pub view: u64,
+    pub height: u32,
+    pub block_hash: Digest,
+    pub vote: ValidatorSignature,
+}
+
+/// The validator set this gadget requires a quorum from, with each validator's voting stake.
+#[derive(Debug, Clone, Default)]
+pub struct ValidatorSet {
+    stake: HashMap<ValidatorId, u64>,
+}
+
+impl ValidatorSet {
+    pub fn new(stake: HashMap<ValidatorId, u64>) -> Self {
+        Self { stake }
+    }
+
+    fn total_stake(&self) -> u64 {
+        self.stake.values().sum()
+    }
+
+    fn stake_of(&self, validator: &ValidatorId) -> u64 {
+        self.stake.get(validator).copied().unwrap_or(0)
+    }
+
+    /// `true` once `stake` exceeds two-thirds of the total validator stake.
+    fn has_quorum(&self, stake: u64) -> bool {
+        // stake * 3 > total * 2, rearranged to avoid floating point.
+        stake * 3 > self.total_stake() * 2
+    }
+}
+
+/// Runs the prevote/precommit vote counting for a single `(height, block_hash)` candidate.
+#[derive(Debug, Clone, Default)]
+struct VoteTally {
+    prevotes: HashMap<ValidatorId, ()>,
+    precommits: HashMap<ValidatorId, ()>,
+}
+
+/// The BFT finality gadget. Tracks the current view and every height finalized by a precommit
+/// quorum so far, keyed by height; `ConsensusInner` consults `finalized_hash` to keep
+/// `scan_forks`, and `finalized_height` to keep the commit path, from reconsidering anything
+/// at or below it, and
+/// `is_finalized_ancestor` to check a specific height against the block this gadget actually
+/// finalized there (not just whether it's below the tip of finality).
+pub struct FinalityGadget {
+    validators: ValidatorSet,
+    view: u64,
+    /// Every height finalized so far, in ascending order, mapped to the hash finalized there.
+    finalized: BTreeMap<u32, Digest>,
+    tallies: HashMap<(u64, u32, Digest), VoteTally>,
+}
+
+impl FinalityGadget {
+    pub fn new(validators: ValidatorSet) -> Self {
+        Self {
+            validators,
+            view: 0,
+            finalized: BTreeMap::new(),
+            tallies: HashMap::new(),
+        }
+    }
+
+    /// The highest height for which a precommit quorum has been observed. `scan_forks` and
+    /// the commit path must refuse to consider forks that branch at or below this height.
+    pub fn finalized_height(&self) -> u32 {
+        self.finalized.keys().next_back().copied().unwrap_or(0)
+    }
+
+    pub fn finalized_hash(&self) -> Option<&Digest> {
+        self.finalized.values().next_back()
+    }
+
+    pub fn view(&self) -> u64 {
+        self.view
+    }
+
+    /// Records a prevote. Does not itself advance finality; precommits are only cast once a
+    /// validator observes a prevote quorum, which callers are expected to drive externally
+    /// (e.g. the proposer's own vote-counting) before broadcasting their `Precommit`.
+    pub fn handle_prevote(&mut self, prevote: Prevote) {
+        if prevote.view < self.view || prevote.height <= self.finalized_height() {
+            return;
+        }
+
+        let key = (prevote.view, prevote.height, prevote.block_hash);
+        self.tallies
+            .entry(key)
+            .or_default()
+            .prevotes
+            .insert(prevote.vote.validator, ());
+    }
+
+    /// Records a precommit; if this pushes the `(height, block_hash)` candidate past a
+    /// two-thirds stake quorum, finalizes it.
+    pub fn handle_precommit(&mut self, precommit: Precommit) -> bool {
+        if precommit.view < self.view || precommit.height <= self.finalized_height() {
+            return false;
+        }
+
+        let key = (precommit.view, precommit.height, precommit.block_hash.clone());
+        let tally = self.tallies.entry(key).or_default();
+        tally.precommits.insert(precommit.vote.validator, ());
+
+        let stake: u64 = tally.precommits.keys().map(|v| self.validators.stake_of(v)).sum();
+
+        if self.validators.has_quorum(stake) {
+            self.finalized.insert(precommit.height, precommit.block_hash);
+            self.tallies.retain(|(_, height, _), _| *height > precommit.height);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Called when the current view's proposer stalls; advances the view so a new proposer
+    /// can re-propose, preserving liveness.
+    pub fn on_view_timeout(&mut self) {
+        self.view += 1;
+    }
+
+    /// Returns `true` if `(height, block_hash)` is the block this gadget actually finalized at
+    /// `height`. Only heights this gadget has itself finalized can be checked this way — a
+    /// height below the earliest one ever finalized here (e.g. from before this node had a
+    /// validator set configured) can't be verified and is not considered an ancestor.
+    pub fn is_finalized_ancestor(&self, height: u32, block_hash: &Digest) -> bool {
+        self.finalized.get(&height) == Some(block_hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validator(id: u8) -> ValidatorId {
+        vec![id]
+    }
+
+    fn signed(id: u8) -> ValidatorSignature {
+        ValidatorSignature {
+            validator: validator(id),
+            signature: vec![],
+        }
+    }
+
+    fn gadget() -> FinalityGadget {
+        let mut stake = HashMap::new();
+        stake.insert(validator(1), 1);
+        stake.insert(validator(2), 1);
+        stake.insert(validator(3), 1);
+        FinalityGadget::new(ValidatorSet::new(stake))
+    }
+
+    #[test]
+    fn precommit_quorum_finalizes_the_height() {
+        let mut gadget = gadget();
+        let block_hash: Digest = [1u8; 32].into();
+
+        assert!(!gadget.handle_precommit(Precommit {
+            view: 0,
+            height: 1,
+            block_hash: block_hash.clone(),
+            vote: signed(1),
+        }));
+        assert_eq!(gadget.finalized_height(), 0);
+
+        // two of three equal-stake validators is exactly two-thirds, not more than it.
+        assert!(!gadget.handle_precommit(Precommit {
+            view: 0,
+            height: 1,
+            block_hash: block_hash.clone(),
+            vote: signed(2),
+        }));
+        assert_eq!(gadget.finalized_height(), 0);
+
+        assert!(gadget.handle_precommit(Precommit {
+            view: 0,
+            height: 1,
+            block_hash: block_hash.clone(),
+            vote: signed(3),
+        }));
+
+        assert_eq!(gadget.finalized_height(), 1);
+        assert_eq!(gadget.finalized_hash(), Some(&block_hash));
+    }
+
+    #[test]
+    fn precommits_below_quorum_do_not_finalize() {
+        let mut gadget = gadget();
+        let block_hash: Digest = [1u8; 32].into();
+
+        assert!(!gadget.handle_precommit(Precommit {
+            view: 0,
+            height: 1,
+            block_hash,
+            vote: signed(1),
+        }));
+
+        assert_eq!(gadget.finalized_height(), 0);
+        assert_eq!(gadget.finalized_hash(), None);
+    }
+
+    #[test]
+    fn is_finalized_ancestor_rejects_a_different_hash_at_a_finalized_height() {
+        let mut gadget = gadget();
+        let real_hash: Digest = [1u8; 32].into();
+        let other_hash: Digest = [2u8; 32].into();
+
+        gadget.handle_precommit(Precommit {
+            view: 0,
+            height: 1,
+            block_hash: real_hash.clone(),
+            vote: signed(1),
+        });
+        gadget.handle_precommit(Precommit {
+            view: 0,
+            height: 1,
+            block_hash: real_hash.clone(),
+            vote: signed(2),
+        });
+        gadget.handle_precommit(Precommit {
+            view: 0,
+            height: 1,
+            block_hash: real_hash.clone(),
+            vote: signed(3),
+        });
+
+        assert!(gadget.is_finalized_ancestor(1, &real_hash));
+        assert!(!gadget.is_finalized_ancestor(1, &other_hash));
+    }
+}
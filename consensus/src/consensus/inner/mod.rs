@@ -18,7 +18,7 @@ use std::sync::Arc;
 
 use crate::{
     error::ConsensusError,
-    memory_pool::MempoolEntry,
+    memory_pool::{MempoolEntry, MemoryPoolStats},
     Consensus,
     ConsensusParameters,
     CreatePartialTransactionRequest,
@@ -26,6 +26,8 @@ use crate::{
     MemoryPool,
 };
 use anyhow::*;
+use log::debug;
+use metrics::gauge;
 use snarkos_storage::{
     BlockFilter,
     BlockOrder,
@@ -49,7 +51,10 @@ use snarkos_metrics::misc::*;
 
 use rand::thread_rng;
 
-use super::message::{ConsensusMessage, CreateTransactionRequest, TransactionResponse};
+use super::{
+    finality::FinalityGadget,
+    message::{ConsensusMessage, CreateTransactionRequest, TransactionResponse},
+};
 
 mod agent;
 mod commit;
@@ -60,6 +65,10 @@ pub struct ConsensusInner {
     pub ledger: DynLedger,
     pub memory_pool: MemoryPool,
     pub storage: DynStorage,
+    /// The BFT finality gadget, if this node is configured with a validator set to vote
+    /// alongside the longest-chain fork choice rule. `None` means no height is pinned and
+    /// `scan_forks` behaves exactly as before.
+    pub finality: Option<FinalityGadget>,
 }
 
 impl ConsensusInner {
@@ -84,6 +93,15 @@ impl ConsensusInner {
             // windows will ignore last block (furthest down), so we pull one extra above
             let target_hash = &canon_hashes[1];
             let ignore_child_hash = &canon_hashes[0];
+
+            // a finalized height is non-revertible: stop walking back once we reach it, so no
+            // fork branching at or below it is ever reported.
+            if let Some(finality) = &self.finality {
+                if Some(target_hash) == finality.finalized_hash() {
+                    break;
+                }
+            }
+
             let children = self.storage.get_block_children(target_hash).await?;
             if children.len() == 1 && &children[0] == ignore_child_hash {
                 continue;
@@ -98,6 +116,84 @@ impl ConsensusInner {
         Ok(known_forks)
     }
 
+    /// Switches the canon chain to the fork tipped at `fork_tip`, provided it carries more
+    /// cumulative difficulty than the current canon chain. Walks back to the common ancestor,
+    /// reverts the canon blocks above it (undoing their ledger serial-number/commitment/memo
+    /// insertions), applies the fork's blocks in their place, and feeds every transaction from
+    /// the reverted blocks back through `insert_into_mempool` so still-valid transactions
+    /// re-enter the pool instead of being dropped.
+    pub async fn reorganize_to(&mut self, fork_tip: Digest) -> Result<(), ConsensusError> {
+        let fork = self.storage.get_fork_path(&fork_tip, crate::OLDEST_FORK_THRESHOLD as u32).await?;
+
+        let rewind_depth = fork.canon_blocks_above_ancestor.len();
+
+        if rewind_depth_exceeds_threshold(rewind_depth, crate::OLDEST_FORK_THRESHOLD) {
+            return Err(ConsensusError::InvalidBlockHash(fork_tip));
+        }
+
+        if let Some(finality) = &self.finality {
+            let canon_height = self.storage.canon().await?.block_height as u32;
+            if rewind_crosses_finalized_height(canon_height, rewind_depth as u32, finality.finalized_height()) {
+                return Err(ConsensusError::InvalidBlockHash(fork_tip));
+            }
+        }
+
+        let current_difficulty = self.storage.total_difficulty().await?;
+        if fork.total_difficulty <= current_difficulty {
+            return Err(ConsensusError::InvalidBlockHash(fork_tip));
+        }
+
+        let mut orphaned_transactions = vec![];
+
+        // revert the canon blocks above the common ancestor, tip-first, undoing their ledger
+        // insertions and collecting their transactions for mempool re-injection.
+        for block in &fork.canon_blocks_above_ancestor {
+            self.revert_block(block)?;
+            orphaned_transactions.extend(block.transactions.iter().cloned());
+        }
+
+        // apply the fork's blocks, oldest first, in place of the reverted canon blocks. Any
+        // block contradicting a height the BFT gadget already finalized is refused outright.
+        for block in &fork.fork_blocks {
+            let block_hash = block.header.hash();
+            if self.conflicts_with_finalized(block.header.height, &block_hash) {
+                return Err(ConsensusError::InvalidBlockHash(block_hash));
+            }
+            self.commit_block(block).await?;
+        }
+
+        let mut reinjected = 0u32;
+        for transaction in orphaned_transactions {
+            if self.insert_into_mempool(transaction)?.is_some() {
+                reinjected += 1;
+            }
+        }
+
+        gauge!("consensus.reorg_depth", rewind_depth as f64);
+        debug!(
+            "reorganized to fork tip {}: rewound {} block(s), reinjected {} orphaned transaction(s)",
+            fork_tip, rewind_depth, reinjected
+        );
+
+        Ok(())
+    }
+
+    /// Undoes a single canon block's ledger insertions: its serial numbers, commitments, and
+    /// memo are removed from the ledger's index so the block is no longer considered spent.
+    fn revert_block(&mut self, block: &SerialBlock) -> Result<(), ConsensusError> {
+        for transaction in &block.transactions {
+            for sn in &transaction.old_serial_numbers {
+                self.ledger.remove_serial(sn)?;
+            }
+            for cm in &transaction.new_commitments {
+                self.ledger.remove_commitment(cm)?;
+            }
+            self.ledger.remove_memo(&transaction.memorandum)?;
+        }
+
+        Ok(())
+    }
+
     /// Adds entry to memory pool if valid in the current ledger.
     pub(crate) fn insert_into_mempool(
         &mut self,
@@ -130,32 +226,47 @@ impl ConsensusInner {
             return Ok(None);
         }
 
-        for sn in &transaction.old_serial_numbers {
+        let entry = MempoolEntry {
+            size_in_bytes: transaction.size(),
+            transaction,
+        };
+
+        // Ranks the incoming transaction against the pool and evicts the lowest fee-per-byte
+        // entries to make room; rejects the incoming transaction instead if it cannot fit
+        // without evicting an entry that ranks at or above it.
+        let evicted = match self.memory_pool.try_insert(transaction_id.clone(), entry.clone()) {
+            Some(evicted) => evicted,
+            None => return Ok(None),
+        };
+
+        if !evicted.is_empty() {
+            debug!("evicted {} low fee-per-byte transaction(s) from the mempool", evicted.len());
+        }
+
+        for sn in &entry.transaction.old_serial_numbers {
             self.memory_pool.serial_numbers.insert(sn.clone());
         }
 
-        for cm in &transaction.new_commitments {
+        for cm in &entry.transaction.new_commitments {
             self.memory_pool.commitments.insert(cm.clone());
         }
 
-        self.memory_pool.memos.insert(transaction.memorandum.clone());
-
-        self.memory_pool
-            .transactions
-            .insert(transaction_id.clone(), MempoolEntry {
-                size_in_bytes: transaction.size(),
-                transaction,
-            });
+        self.memory_pool.memos.insert(entry.transaction.memorandum.clone());
 
         Ok(Some(transaction_id))
     }
 
-    /// Cleanse the memory pool of outdated transactions.
+    /// Cleanse the memory pool of outdated transactions, re-inserting survivors in
+    /// descending fee-per-byte order so the highest-paying transactions are retained if the
+    /// pool is over capacity.
     pub(crate) fn cleanse_memory_pool(&mut self) -> Result<(), ConsensusError> {
-        let old_mempool = std::mem::take(&mut self.memory_pool);
+        let old_mempool = std::mem::replace(&mut self.memory_pool, MemoryPool::with_capacity(0));
+        self.memory_pool = MemoryPool::with_capacity(old_mempool.capacity_bytes());
 
-        for (_, entry) in &old_mempool.transactions {
-            if let Err(e) = self.insert_into_mempool(entry.transaction.clone()) {
+        for entry in old_mempool.entries_by_descending_fee() {
+            if let Err(e) = self.insert_into_mempool(entry.transaction) {
+                // leave the old mempool in place rather than losing every transaction that
+                // hadn't been reinserted yet.
                 self.memory_pool = old_mempool;
                 return Err(e);
             }
@@ -163,4 +274,105 @@ impl ConsensusInner {
 
         Ok(())
     }
+
+    /// Returns `true` if `(height, block_hash)` contradicts a height already finalized by the
+    /// BFT gadget, i.e. it is at or below the finalized height but is not the finalized block
+    /// itself. The commit path must refuse any block for which this returns `true`.
+    pub fn conflicts_with_finalized(&self, height: u32, block_hash: &Digest) -> bool {
+        match &self.finality {
+            Some(finality) => height <= finality.finalized_height() && !finality.is_finalized_ancestor(height, block_hash),
+            None => false,
+        }
+    }
+
+    /// Returns a snapshot of the current mempool occupancy, for `ConsensusMessage::MempoolStats`
+    /// RPC/peer callers that want live telemetry without reaching into the pool's index sets.
+    pub fn mempool_stats(&self) -> MemoryPoolStats {
+        self.memory_pool.stats()
+    }
+
+    /// Enumerates the ids of every unconfirmed transaction currently tracked by the mempool.
+    pub fn mempool_transaction_ids(&self) -> Vec<Digest> {
+        self.memory_pool.transactions.keys().cloned().collect()
+    }
+
+    /// Dispatches a single `ConsensusMessage` from the agent's inbox, replying on the message's
+    /// embedded channel, where it has one, so a peer/RPC caller gets its result back.
+    pub(crate) async fn handle_message(&mut self, message: ConsensusMessage) {
+        match message {
+            ConsensusMessage::MempoolStats(reply) => {
+                let _ = reply.send(self.mempool_stats());
+            }
+            ConsensusMessage::MempoolTransactionIds(reply) => {
+                let _ = reply.send(self.mempool_transaction_ids());
+            }
+            ConsensusMessage::CreateTransaction(request, reply) => match self.create_transaction(request).await {
+                Ok(response) => {
+                    let _ = reply.send(response);
+                }
+                Err(e) => debug!("failed to create transaction: {}", e),
+            },
+            ConsensusMessage::Prevote(prevote) => {
+                if let Some(finality) = &mut self.finality {
+                    finality.handle_prevote(prevote);
+                }
+            }
+            ConsensusMessage::Precommit(precommit) => {
+                if let Some(finality) = &mut self.finality {
+                    finality.handle_precommit(precommit);
+                }
+            }
+            ConsensusMessage::CommitBlock(block, reply) => {
+                let block_hash = block.header.hash();
+                let result = if self.conflicts_with_finalized(block.header.height, &block_hash) {
+                    Err(ConsensusError::InvalidBlockHash(block_hash))
+                } else {
+                    self.commit_block(&block).await
+                };
+                let _ = reply.send(result);
+            }
+        }
+    }
+}
+
+/// `true` if rewinding `rewind_depth` canon blocks to reach a fork's common ancestor would
+/// walk back further than `OLDEST_FORK_THRESHOLD`, beyond which a reorg is refused outright.
+fn rewind_depth_exceeds_threshold(rewind_depth: usize, oldest_fork_threshold: usize) -> bool {
+    rewind_depth > oldest_fork_threshold
+}
+
+/// `true` if rewinding `rewind_depth` blocks from `canon_height` would drop the canon chain
+/// below `finalized_height`, i.e. the reorg would revert a height the BFT gadget has already
+/// finalized.
+fn rewind_crosses_finalized_height(canon_height: u32, rewind_depth: u32, finalized_height: u32) -> bool {
+    canon_height.saturating_sub(rewind_depth) < finalized_height
+}
+
+#[cfg(test)]
+mod reorg_guard_tests {
+    use super::*;
+
+    #[test]
+    fn rewind_within_threshold_is_allowed() {
+        assert!(!rewind_depth_exceeds_threshold(5, 10));
+        assert!(!rewind_depth_exceeds_threshold(10, 10));
+    }
+
+    #[test]
+    fn rewind_beyond_threshold_is_refused() {
+        assert!(rewind_depth_exceeds_threshold(11, 10));
+    }
+
+    #[test]
+    fn rewind_stopping_at_or_above_finalized_height_is_allowed() {
+        // canon at 100, rewinding 5 lands at 95, which is still at/above the finalized height.
+        assert!(!rewind_crosses_finalized_height(100, 5, 95));
+        assert!(!rewind_crosses_finalized_height(100, 5, 90));
+    }
+
+    #[test]
+    fn rewind_crossing_below_finalized_height_is_refused() {
+        // canon at 100, rewinding 10 lands at 90, which is below the finalized height of 95.
+        assert!(rewind_crosses_finalized_height(100, 10, 95));
+    }
 }
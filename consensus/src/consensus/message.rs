@@ -0,0 +1,86 @@
+// Copyright (C) 2019-2021 Aleo Systems Inc.
+// This file is part of the snarkOS library.
+
+// The snarkOS library is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// The snarkOS library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with the snarkOS library. If not, see <https://www.gnu.org/licenses/>.
+
+//! Requests the consensus agent task accepts from peers/RPC callers. Those that expect an answer
+//! carry a reply channel so the caller gets a response back without blocking the agent's own
+//! event loop; the finality votes (`Prevote`, `Precommit`) carry none.
+
+use tokio::sync::{mpsc, oneshot};
+
+use super::finality::{Precommit, Prevote};
+use crate::{error::ConsensusError, memory_pool::MemoryPoolStats, CreatePartialTransactionRequest};
+use snarkos_storage::{Digest, SerialBlock};
+
+/// A request to assemble and broadcast a new transaction.
+pub struct CreateTransactionRequest {
+    pub request: CreatePartialTransactionRequest,
+}
+
+/// The result of handling a [`CreateTransactionRequest`].
+pub struct TransactionResponse {
+    pub transaction_id: Digest,
+}
+
+/// A message delivered to the consensus agent task.
+pub enum ConsensusMessage {
+    /// Assembles and broadcasts a new transaction.
+    CreateTransaction(CreateTransactionRequest, oneshot::Sender<TransactionResponse>),
+    /// Fetches a snapshot of mempool occupancy, for RPC/peer monitoring.
+    MempoolStats(oneshot::Sender<MemoryPoolStats>),
+    /// Enumerates the ids of every unconfirmed transaction in the mempool.
+    MempoolTransactionIds(oneshot::Sender<Vec<Digest>>),
+    /// A validator's first-round BFT finality vote for a canon block.
+    Prevote(Prevote),
+    /// A validator's second-round BFT finality vote for a canon block.
+    Precommit(Precommit),
+    /// Verifies and commits a block learned from a peer, e.g. via `snarkos_network`'s sync
+    /// engine, through the same pipeline `reorganize_to` applies fork blocks with.
+    CommitBlock(SerialBlock, oneshot::Sender<Result<(), ConsensusError>>),
+}
+
+/// A cloneable handle other subsystems use to submit requests to the consensus agent task,
+/// without depending on `ConsensusInner` directly or running on its task.
+#[derive(Clone)]
+pub struct ConsensusHandle {
+    messages: mpsc::Sender<ConsensusMessage>,
+}
+
+impl ConsensusHandle {
+    pub fn new(messages: mpsc::Sender<ConsensusMessage>) -> Self {
+        Self { messages }
+    }
+
+    /// Verifies and commits `block` through the consensus agent, returning once it has been
+    /// applied or rejected.
+    pub async fn commit_block(&self, block: SerialBlock) -> Result<(), ConsensusHandleError> {
+        let (reply, reply_rx) = oneshot::channel();
+
+        self.messages
+            .send(ConsensusMessage::CommitBlock(block, reply))
+            .await
+            .map_err(|_| ConsensusHandleError::AgentStopped)?;
+
+        reply_rx.await.map_err(|_| ConsensusHandleError::AgentStopped)?.map_err(ConsensusHandleError::Consensus)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConsensusHandleError {
+    #[error("the consensus agent's task has stopped")]
+    AgentStopped,
+    #[error(transparent)]
+    Consensus(#[from] ConsensusError),
+}
@@ -0,0 +1,301 @@
+// Copyright (C) 2019-2021 Aleo Systems Inc.
+// This file is part of the snarkOS library.
+
+// The snarkOS library is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// The snarkOS library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with the snarkOS library. If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::{HashMap, HashSet};
+
+use snarkos_storage::{Digest, SerialTransaction};
+
+/// Default ceiling on the number of bytes the mempool is allowed to hold before it starts
+/// evicting the lowest fee-per-byte transactions.
+pub const DEFAULT_MEMORY_POOL_CAPACITY_BYTES: u64 = 128 * 1024 * 1024;
+
+/// A transaction tracked by the `MemoryPool`, along with the bookkeeping needed to
+/// rank it against its peers for eviction purposes.
+#[derive(Debug, Clone)]
+pub struct MempoolEntry {
+    pub transaction: SerialTransaction,
+    pub size_in_bytes: usize,
+}
+
+impl MempoolEntry {
+    /// The fee this entry pays per byte of serialized transaction size, used to rank
+    /// entries for eviction when the pool is over capacity.
+    fn fee_per_byte(&self) -> u64 {
+        let fee = self.transaction.value_balance.max(0) as u64;
+        if self.size_in_bytes == 0 {
+            fee
+        } else {
+            fee / self.size_in_bytes as u64
+        }
+    }
+}
+
+/// A key used to order mempool entries by fee-per-byte, breaking ties by transaction id so
+/// the ordering stays total and deterministic.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct FeeRank {
+    fee_per_byte: u64,
+    transaction_id: Digest,
+}
+
+/// An unconfirmed transaction pool, bounded to `capacity_bytes`. Entries are ranked by
+/// fee-per-byte so that once the pool is full, inserting a new transaction evicts the
+/// lowest-paying entries rather than growing without bound.
+pub struct MemoryPool {
+    pub transactions: HashMap<Digest, MempoolEntry>,
+    pub serial_numbers: HashSet<Vec<u8>>,
+    pub commitments: HashSet<Vec<u8>>,
+    pub memos: HashSet<[u8; 32]>,
+
+    /// Fee rank of every tracked transaction, kept in ascending order so the cheapest
+    /// entries to evict are at the front.
+    rank: std::collections::BTreeSet<FeeRank>,
+    /// Maximum combined `size_in_bytes` of all tracked transactions.
+    capacity_bytes: u64,
+    /// Combined `size_in_bytes` of all tracked transactions.
+    total_size_in_bytes: u64,
+}
+
+impl Default for MemoryPool {
+    fn default() -> Self {
+        Self::with_capacity(DEFAULT_MEMORY_POOL_CAPACITY_BYTES)
+    }
+}
+
+impl MemoryPool {
+    /// Creates an empty memory pool bounded to `capacity_bytes` of combined transaction size.
+    pub fn with_capacity(capacity_bytes: u64) -> Self {
+        Self {
+            transactions: HashMap::new(),
+            serial_numbers: HashSet::new(),
+            commitments: HashSet::new(),
+            memos: HashSet::new(),
+            rank: std::collections::BTreeSet::new(),
+            capacity_bytes,
+            total_size_in_bytes: 0,
+        }
+    }
+
+    /// Returns the configured capacity, in bytes.
+    pub fn capacity_bytes(&self) -> u64 {
+        self.capacity_bytes
+    }
+
+    /// Returns the combined size, in bytes, of every transaction currently tracked.
+    pub fn total_size_in_bytes(&self) -> u64 {
+        self.total_size_in_bytes
+    }
+
+    /// Plans which entries to evict to make room for `needed_bytes` more, without mutating
+    /// the pool. Only ever plans to evict entries that rank *below* `incoming` — as soon as
+    /// the next-weakest remaining entry would rank at or above `incoming`, planning stops.
+    /// Returns `None` if there isn't enough weaker stock to evict to fit `incoming`, meaning
+    /// it must be rejected outright rather than displacing something that outranks it.
+    fn plan_eviction(&self, incoming: &FeeRank, needed_bytes: u64) -> Option<Vec<FeeRank>> {
+        let mut to_evict = vec![];
+        let mut freed_bytes = 0u64;
+
+        for candidate in &self.rank {
+            if self.total_size_in_bytes + needed_bytes <= self.capacity_bytes + freed_bytes {
+                break;
+            }
+
+            if candidate >= incoming {
+                // every remaining entry ranks at or above the incoming transaction; there's
+                // nothing weaker left to evict, so the incoming transaction can't be admitted.
+                return None;
+            }
+
+            if let Some(evicted_entry) = self.transactions.get(&candidate.transaction_id) {
+                freed_bytes += evicted_entry.size_in_bytes as u64;
+            }
+            to_evict.push(candidate.clone());
+        }
+
+        if self.total_size_in_bytes + needed_bytes > self.capacity_bytes + freed_bytes {
+            return None;
+        }
+
+        Some(to_evict)
+    }
+
+    /// Attempts to make room for `entry` and, if there is space (after evicting any
+    /// lower-ranked entries), inserts it. Returns the ids of any transactions evicted to make
+    /// room, or `None` if `entry` itself was rejected: either it is larger than the whole
+    /// capacity, or evicting only entries that rank below it would not free enough room.
+    pub fn try_insert(&mut self, transaction_id: Digest, entry: MempoolEntry) -> Option<Vec<Digest>> {
+        let incoming_rank = FeeRank {
+            fee_per_byte: entry.fee_per_byte(),
+            transaction_id: transaction_id.clone(),
+        };
+        let needed_bytes = entry.size_in_bytes as u64;
+
+        if needed_bytes > self.capacity_bytes {
+            return None;
+        }
+
+        let to_evict = if self.total_size_in_bytes + needed_bytes > self.capacity_bytes {
+            self.plan_eviction(&incoming_rank, needed_bytes)?
+        } else {
+            vec![]
+        };
+
+        let mut evicted_ids = vec![];
+        for victim in to_evict {
+            self.rank.remove(&victim);
+            if let Some(evicted_entry) = self.transactions.remove(&victim.transaction_id) {
+                self.total_size_in_bytes -= evicted_entry.size_in_bytes as u64;
+
+                for sn in &evicted_entry.transaction.old_serial_numbers {
+                    self.serial_numbers.remove(sn);
+                }
+                for cm in &evicted_entry.transaction.new_commitments {
+                    self.commitments.remove(cm);
+                }
+                self.memos.remove(&evicted_entry.transaction.memorandum);
+            }
+            evicted_ids.push(victim.transaction_id);
+        }
+
+        for sn in &entry.transaction.old_serial_numbers {
+            self.serial_numbers.insert(sn.clone());
+        }
+        for cm in &entry.transaction.new_commitments {
+            self.commitments.insert(cm.clone());
+        }
+        self.memos.insert(entry.transaction.memorandum);
+
+        self.total_size_in_bytes += needed_bytes;
+        self.rank.insert(incoming_rank);
+        self.transactions.insert(transaction_id, entry);
+
+        Some(evicted_ids)
+    }
+
+    /// Returns the tracked entries in descending fee-per-byte order, highest-paying first.
+    pub fn entries_by_descending_fee(&self) -> Vec<MempoolEntry> {
+        self.rank
+            .iter()
+            .rev()
+            .filter_map(|rank| self.transactions.get(&rank.transaction_id))
+            .cloned()
+            .collect()
+    }
+
+    /// Computes a snapshot of the pool's current occupancy.
+    pub fn stats(&self) -> MemoryPoolStats {
+        let total_fee = self
+            .transactions
+            .values()
+            .map(|entry| entry.transaction.value_balance.max(0) as u64)
+            .sum();
+
+        MemoryPoolStats {
+            unconfirmed_transactions: self.transactions.len() as u32,
+            total_size_in_bytes: self.total_size_in_bytes,
+            total_serial_numbers: self.serial_numbers.len() as u32,
+            total_commitments: self.commitments.len() as u32,
+            total_memos: self.memos.len() as u32,
+            total_fee,
+        }
+    }
+}
+
+/// A snapshot of mempool occupancy, suitable for RPC/peer introspection.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryPoolStats {
+    pub unconfirmed_transactions: u32,
+    pub total_size_in_bytes: u64,
+    pub total_serial_numbers: u32,
+    pub total_commitments: u32,
+    pub total_memos: u32,
+    pub total_fee: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tx(id_byte: u8, value_balance: i64) -> SerialTransaction {
+        SerialTransaction {
+            id: [id_byte; 32],
+            old_serial_numbers: vec![vec![id_byte]],
+            new_commitments: vec![vec![id_byte.wrapping_add(1)]],
+            memorandum: [id_byte; 32],
+            value_balance,
+            ..Default::default()
+        }
+    }
+
+    fn entry(id_byte: u8, value_balance: i64, size_in_bytes: usize) -> (Digest, MempoolEntry) {
+        let transaction = tx(id_byte, value_balance);
+        (transaction.id.into(), MempoolEntry { transaction, size_in_bytes })
+    }
+
+    #[test]
+    fn rejects_incoming_rather_than_evicting_a_higher_fee_entry() {
+        let mut pool = MemoryPool::with_capacity(100);
+
+        let (id_a, entry_a) = entry(1, 10, 10); // fee/byte 1
+        let (id_b, entry_b) = entry(2, 9_000, 90); // fee/byte 100
+        assert!(pool.try_insert(id_a.clone(), entry_a).is_some());
+        assert!(pool.try_insert(id_b.clone(), entry_b).is_some());
+
+        let (id_c, entry_c) = entry(3, 100, 50); // fee/byte 2
+        assert_eq!(pool.try_insert(id_c.clone(), entry_c), None);
+
+        // neither existing entry was evicted, and the rejected transaction was not admitted.
+        assert!(pool.transactions.contains_key(&id_a));
+        assert!(pool.transactions.contains_key(&id_b));
+        assert!(!pool.transactions.contains_key(&id_c));
+    }
+
+    #[test]
+    fn evicts_only_as_many_low_fee_entries_as_needed() {
+        let mut pool = MemoryPool::with_capacity(100);
+
+        let (id_a, entry_a) = entry(1, 10, 10); // fee/byte 1
+        let (id_b, entry_b) = entry(2, 200, 40); // fee/byte 5
+        assert!(pool.try_insert(id_a.clone(), entry_a).is_some());
+        assert!(pool.try_insert(id_b.clone(), entry_b).is_some());
+
+        let (id_d, entry_d) = entry(4, 600, 60); // fee/byte 10
+        let evicted = pool.try_insert(id_d.clone(), entry_d).unwrap();
+
+        assert_eq!(evicted, vec![id_a.clone()]);
+        assert!(!pool.transactions.contains_key(&id_a));
+        assert!(pool.transactions.contains_key(&id_b));
+        assert!(pool.transactions.contains_key(&id_d));
+    }
+
+    #[test]
+    fn stats_reflect_tracked_entries() {
+        let mut pool = MemoryPool::with_capacity(1_000);
+
+        let (id_a, entry_a) = entry(1, 10, 10);
+        let (id_b, entry_b) = entry(2, 25, 20);
+        pool.try_insert(id_a, entry_a).unwrap();
+        pool.try_insert(id_b, entry_b).unwrap();
+
+        let stats = pool.stats();
+        assert_eq!(stats.unconfirmed_transactions, 2);
+        assert_eq!(stats.total_size_in_bytes, 30);
+        assert_eq!(stats.total_serial_numbers, 2);
+        assert_eq!(stats.total_commitments, 2);
+        assert_eq!(stats.total_memos, 2);
+        assert_eq!(stats.total_fee, 35);
+    }
+}
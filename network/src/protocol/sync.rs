@@ -0,0 +1,87 @@
+// Copyright (C) 2019-2021 Aleo Systems Inc.
+// This file is part of the snarkOS library.
+
+// The snarkOS library is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// The snarkOS library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with the snarkOS library. If not, see <https://www.gnu.org/licenses/>.
+
+//! Drives the `GetBlock`/`GetSync` request side of syncing with a single peer. `SyncHandler`
+//! only decides *when* to issue a request; what it learns about (a header to chase, the height
+//! to catch up to) is handed off to a `SyncService`, and blocks are verified and imported by
+//! the `SyncingEngine` on its own task, off this read loop.
+
+use std::{net::SocketAddr, sync::Arc};
+
+use anyhow::*;
+use snarkos_objects::BlockHeaderHash;
+use snarkos_storage::DynStorage;
+
+use crate::{
+    message::{
+        types::{GetBlock, GetSync},
+        Channel,
+        Message,
+    },
+    syncing::SyncService,
+};
+
+/// Requests the next piece of the chain from a single peer: a specific block if one is queued
+/// in `block_headers`, otherwise a fresh `GetSync`.
+pub struct SyncHandler {
+    pub block_headers: Vec<BlockHeaderHash>,
+    bootnode_address: SocketAddr,
+    target_height: u32,
+    sync_service: SyncService,
+}
+
+impl SyncHandler {
+    /// Creates a handler that forwards everything it learns about to `sync_service`, so
+    /// verification/import happens on the `SyncingEngine`'s task instead of inline here.
+    pub fn new(bootnode_address: SocketAddr, sync_service: SyncService) -> Self {
+        Self {
+            block_headers: vec![],
+            bootnode_address,
+            target_height: 0,
+            sync_service,
+        }
+    }
+
+    pub fn bootnode_address(&self) -> SocketAddr {
+        self.bootnode_address
+    }
+
+    /// Records the height this node is trying to catch up to (learned from a peer's
+    /// `GetSync`/`HeadersResponse` reply) and forwards it to the `SyncService` so
+    /// `SyncEvent::SyncProgress` can report real progress instead of falling back to the height
+    /// of whatever block was last imported.
+    pub async fn update_syncing(&mut self, target_height: u32) {
+        self.target_height = target_height;
+        self.sync_service.set_target_height(target_height).await.ok();
+    }
+
+    /// Issues the next sync request on `channel`. Requesting a block for a queued header also
+    /// enqueues that header with the `SyncService`, so the `SyncingEngine` can track it and
+    /// import the response off this task once it arrives.
+    pub async fn increment(&mut self, channel: Arc<Channel>, _storage: DynStorage) -> Result<()> {
+        match self.block_headers.first().cloned() {
+            Some(hash) => {
+                channel.write(&GetBlock::new(hash.clone())).await?;
+                self.sync_service.enqueue_header(hash).await.ok();
+            }
+            None => {
+                channel.write(&GetSync::new(vec![])).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
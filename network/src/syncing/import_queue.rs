@@ -0,0 +1,66 @@
+// Copyright (C) 2019-2021 Aleo Systems Inc.
+// This file is part of the snarkOS library.
+
+// The snarkOS library is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// The snarkOS library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with the snarkOS library. If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::VecDeque;
+
+use snarkos_objects::BlockHeaderHash;
+use snarkos_storage::SerialBlock;
+
+/// Headers and blocks awaiting verification/import, kept separate from the network read loop
+/// so a burst of incoming blocks backpressures the `mpsc` channel feeding it rather than
+/// stalling message handling.
+#[derive(Default)]
+pub struct ImportQueue {
+    pending_headers: VecDeque<BlockHeaderHash>,
+    pending_blocks: VecDeque<SerialBlock>,
+    target_height: Option<u32>,
+}
+
+impl ImportQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push_header(&mut self, hash: BlockHeaderHash) {
+        self.pending_headers.push_back(hash);
+    }
+
+    pub fn pop_header(&mut self) -> Option<BlockHeaderHash> {
+        self.pending_headers.pop_front()
+    }
+
+    pub fn push_block(&mut self, block: SerialBlock) {
+        self.pending_blocks.push_back(block);
+    }
+
+    pub fn pop_block(&mut self) -> Option<SerialBlock> {
+        self.pending_blocks.pop_front()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending_headers.is_empty() && self.pending_blocks.is_empty()
+    }
+
+    /// The height the queue is trying to catch up to, if known (set by whoever last learned it
+    /// from a peer's `Sync`/`HeadersResponse`).
+    pub fn target_height(&self) -> Option<u32> {
+        self.target_height
+    }
+
+    pub fn set_target_height(&mut self, height: u32) {
+        self.target_height = Some(height);
+    }
+}
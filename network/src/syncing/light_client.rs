@@ -0,0 +1,158 @@
+// Copyright (C) 2019-2021 Aleo Systems Inc.
+// This file is part of the snarkOS library.
+
+// The snarkOS library is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// The snarkOS library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with the snarkOS library. If not, see <https://www.gnu.org/licenses/>.
+
+//! Header-only sync for light clients: follows and verifies canon without downloading full
+//! transaction data, by requesting header ranges (`GetHeaders`/`HeadersResponse`, alongside the
+//! full client's `GetSync`/`GetBlock` exchange), checking each header's PoSW proof and Merkle
+//! roots and that it extends its parent, rather than running the full `ConsensusInner` commit
+//! pipeline.
+
+use anyhow::*;
+use snarkos_objects::BlockHeaderHash;
+use snarkos_storage::{Digest, SerialBlockHeader};
+use snarkvm_posw::txids_to_roots;
+
+/// A single entry in the light client's header store: the header itself plus
+/// `commitment_tree_root`, the root of the ledger's commitment/serial-number Merkle tree as of
+/// this header. This is distinct from `header.merkle_root_hash`/`header.pedersen_merkle_root_hash`,
+/// which only commit to the block's own transaction ids — a commitment membership proof has to
+/// be checked against the ledger digest, not the txid tree.
+#[derive(Debug, Clone)]
+struct HeaderEntry {
+    header: SerialBlockHeader,
+    commitment_tree_root: Digest,
+}
+
+/// Tracks only headers and their committed tree roots, maintaining a validated view of canon
+/// without the full ledger state a `ConsensusInner` node keeps.
+#[derive(Default)]
+pub struct HeaderStore {
+    by_height: Vec<HeaderEntry>,
+}
+
+impl HeaderStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The height of the highest header this store has verified and accepted.
+    pub fn tip_height(&self) -> Option<u32> {
+        self.by_height.len().checked_sub(1).map(|i| i as u32)
+    }
+
+    pub fn tip_hash(&self) -> Option<BlockHeaderHash> {
+        self.by_height.last().map(|entry| entry.header.hash())
+    }
+
+    /// Verifies `headers` form a chain off the current tip (or off nothing, if the store is
+    /// empty) and appends them. Fails on the first header that doesn't verify; none of the
+    /// batch is appended in that case.
+    pub fn extend_verified(&mut self, headers: Vec<SerialBlockHeader>) -> Result<()> {
+        let mut parent = self.by_height.last().map(|entry| &entry.header);
+
+        for header in &headers {
+            verify_header(header, parent)?;
+            parent = Some(header);
+        }
+
+        self.by_height.extend(headers.into_iter().map(|header| {
+            let commitment_tree_root = header.ledger_digest.clone();
+            HeaderEntry { header, commitment_tree_root }
+        }));
+
+        Ok(())
+    }
+
+    fn entry_at(&self, height: u32) -> Option<&HeaderEntry> {
+        self.by_height.get(height as usize)
+    }
+
+    /// Verifies that `commitment` is a member of the ledger's commitment/serial-number tree as
+    /// committed to by the header at `height` (`HeaderEntry::commitment_tree_root`, not the
+    /// header's transaction-id Merkle root), using `proof` as the Merkle membership path.
+    pub fn verify_commitment_membership(&self, height: u32, commitment: &[u8], proof: &[Vec<u8>]) -> Result<bool> {
+        let entry = self
+            .entry_at(height)
+            .ok_or_else(|| anyhow!("no synced header at height {}", height))?;
+
+        Ok(verify_merkle_membership(&entry.commitment_tree_root, commitment, proof))
+    }
+
+    /// The root of the ledger's commitment/serial-number tree as of `height`, if synced.
+    pub fn commitment_tree_root(&self, height: u32) -> Option<&Digest> {
+        self.entry_at(height).map(|entry| &entry.commitment_tree_root)
+    }
+}
+
+/// Checks that `header`'s PoSW proof is valid, that the Merkle roots `header` commits to match
+/// what `txids_to_roots` recomputes for `header`'s own transaction ids, and, if `parent` is
+/// given, that `header.previous_block_hash` matches `parent`'s hash.
+fn verify_header(header: &SerialBlockHeader, parent: Option<&SerialBlockHeader>) -> Result<()> {
+    if let Some(parent) = parent {
+        ensure!(
+            header.previous_block_hash == parent.hash(),
+            "header at height does not extend the expected parent"
+        );
+    }
+
+    ensure!(header.proof.is_valid(), "header PoSW proof does not verify");
+
+    let (merkle_root, pedersen_merkle_root, _) = txids_to_roots(&header.transaction_ids);
+    ensure!(merkle_root == header.merkle_root_hash, "header merkle root does not match computed root");
+    ensure!(
+        pedersen_merkle_root == header.pedersen_merkle_root_hash,
+        "header pedersen merkle root does not match computed root"
+    );
+
+    Ok(())
+}
+
+/// Recomputes a Merkle root from a leaf and its membership path and checks it against `root`.
+fn verify_merkle_membership(root: &[u8], leaf: &[u8], proof: &[Vec<u8>]) -> bool {
+    let mut current = leaf.to_vec();
+
+    for sibling in proof {
+        let mut hasher_input = Vec::with_capacity(current.len() + sibling.len());
+        if current <= *sibling {
+            hasher_input.extend_from_slice(&current);
+            hasher_input.extend_from_slice(sibling);
+        } else {
+            hasher_input.extend_from_slice(sibling);
+            hasher_input.extend_from_slice(&current);
+        }
+        current = blake2_hash(&hasher_input);
+    }
+
+    current == root
+}
+
+fn blake2_hash(data: &[u8]) -> Vec<u8> {
+    use blake2::{digest::Digest as _, Blake2s256};
+    Blake2s256::digest(data).to_vec()
+}
+
+/// A request for a contiguous range of headers, `[start_height, start_height + count)`.
+#[derive(Debug, Clone)]
+pub struct GetHeaders {
+    pub start_height: u32,
+    pub count: u32,
+}
+
+/// The response to a [`GetHeaders`] request.
+#[derive(Debug, Clone)]
+pub struct HeadersResponse {
+    pub headers: Vec<SerialBlockHeader>,
+}
@@ -0,0 +1,235 @@
+// Copyright (C) 2019-2021 Aleo Systems Inc.
+// This file is part of the snarkOS library.
+
+// The snarkOS library is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// The snarkOS library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with the snarkOS library. If not, see <https://www.gnu.org/licenses/>.
+
+//! Runs chain syncing as a standalone task, independent of the networking read loop.
+//!
+//! `SyncHandler::increment` still decides *when* to issue `GetBlock`/`GetSync`, but the
+//! resulting headers and blocks are handed to the [`SyncingEngine`] through a [`SyncService`]
+//! handle instead of being imported inline. The engine verifies and commits the blocks off the
+//! network thread by routing them through consensus's real verify/commit pipeline (a
+//! [`ConsensusHandle`]) via an [`ImportQueue`], and publishes [`SyncEvent`]s that consensus,
+//! mempool cleansing, and metrics can each subscribe to independently with
+//! [`SyncService::subscribe`].
+
+mod import_queue;
+pub mod light_client;
+
+pub use import_queue::ImportQueue;
+pub use light_client::{GetHeaders, HeaderStore, HeadersResponse};
+
+use std::net::SocketAddr;
+
+use snarkos_consensus::ConsensusHandle;
+use snarkos_objects::BlockHeaderHash;
+use snarkos_storage::{DynStorage, SerialBlock};
+use tokio::sync::{broadcast, mpsc};
+
+/// The default capacity of the [`SyncEvent`] broadcast channel; slow subscribers that fall
+/// behind by more than this many events will miss some and should resync from `ChainSynced`.
+const EVENT_CHANNEL_CAPACITY: usize = 256;
+
+/// A command enqueued onto a running [`SyncingEngine`].
+enum SyncCommand {
+    EnqueueHeader(BlockHeaderHash),
+    EnqueueBlock(SerialBlock),
+    SetTargetHeight(u32),
+    PeerConnected(SocketAddr),
+    PeerDisconnected(SocketAddr),
+}
+
+/// Events published by the [`SyncingEngine`] as syncing progresses. Consensus, mempool
+/// cleansing, and metrics subscribe to these instead of being driven directly from the
+/// network read loop.
+#[derive(Debug, Clone)]
+pub enum SyncEvent {
+    PeerConnected(SocketAddr),
+    PeerDisconnected(SocketAddr),
+    SyncProgress { current_height: u32, target_height: u32 },
+    ChainSynced { height: u32 },
+}
+
+/// A cloneable handle other subsystems use to enqueue headers/blocks with the syncing engine,
+/// without depending on the engine's internals or running on its task.
+#[derive(Clone)]
+pub struct SyncService {
+    commands: mpsc::Sender<SyncCommand>,
+    events: broadcast::Sender<SyncEvent>,
+}
+
+impl SyncService {
+    /// Records a block header learned from the network as pending in the engine's import queue.
+    /// The engine does not request or fetch the block for it; blocks arrive via `enqueue_block`.
+    pub async fn enqueue_header(&self, hash: BlockHeaderHash) -> Result<(), SyncServiceError> {
+        self.commands
+            .send(SyncCommand::EnqueueHeader(hash))
+            .await
+            .map_err(|_| SyncServiceError::EngineStopped)
+    }
+
+    /// Enqueues a full block body for the engine to verify and import.
+    pub async fn enqueue_block(&self, block: SerialBlock) -> Result<(), SyncServiceError> {
+        self.commands
+            .send(SyncCommand::EnqueueBlock(block))
+            .await
+            .map_err(|_| SyncServiceError::EngineStopped)
+    }
+
+    /// Records the height learned from a peer's `GetSync`/`HeadersResponse` reply, so
+    /// `SyncEvent::SyncProgress` reports real progress instead of falling back to the height of
+    /// whatever block was last imported.
+    pub async fn set_target_height(&self, height: u32) -> Result<(), SyncServiceError> {
+        self.commands
+            .send(SyncCommand::SetTargetHeight(height))
+            .await
+            .map_err(|_| SyncServiceError::EngineStopped)
+    }
+
+    pub async fn notify_peer_connected(&self, addr: SocketAddr) -> Result<(), SyncServiceError> {
+        self.commands
+            .send(SyncCommand::PeerConnected(addr))
+            .await
+            .map_err(|_| SyncServiceError::EngineStopped)
+    }
+
+    pub async fn notify_peer_disconnected(&self, addr: SocketAddr) -> Result<(), SyncServiceError> {
+        self.commands
+            .send(SyncCommand::PeerDisconnected(addr))
+            .await
+            .map_err(|_| SyncServiceError::EngineStopped)
+    }
+
+    /// Subscribes to [`SyncEvent`]s independently of any other subscriber. Consensus, mempool
+    /// cleansing, and metrics are each expected to hold their own subscription from their own
+    /// clone of this handle.
+    pub fn subscribe(&self) -> SyncEventStream {
+        SyncEventStream(self.events.subscribe())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SyncServiceError {
+    #[error("the syncing engine's task has stopped")]
+    EngineStopped,
+}
+
+/// A subscription to [`SyncEvent`]s published by a [`SyncingEngine`].
+pub struct SyncEventStream(broadcast::Receiver<SyncEvent>);
+
+impl SyncEventStream {
+    /// Waits for the next event. Returns `None` if the engine has shut down.
+    pub async fn recv(&mut self) -> Option<SyncEvent> {
+        loop {
+            match self.0.recv().await {
+                Ok(event) => return Some(event),
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+/// Owns the sync task: it drains commands from a [`SyncService`], feeds headers/blocks through
+/// an [`ImportQueue`] so verification runs off the network thread, routes queued blocks through
+/// consensus's real verify/commit pipeline via a [`ConsensusHandle`], and broadcasts
+/// [`SyncEvent`]s to subscribers. Construct with [`SyncingEngine::spawn`], which returns the
+/// handle and the join handle for the background task together.
+pub struct SyncingEngine {
+    commands: mpsc::Receiver<SyncCommand>,
+    events: broadcast::Sender<SyncEvent>,
+    import_queue: ImportQueue,
+    storage: DynStorage,
+    consensus: ConsensusHandle,
+}
+
+impl SyncingEngine {
+    /// Spawns the engine's task and returns a [`SyncService`] handle plus an [`SyncEventStream`]
+    /// for the caller's own subscription. Additional subscribers (e.g. consensus, mempool
+    /// cleansing, metrics) each get their own independent stream by calling
+    /// [`SyncService::subscribe`] on a clone of the returned handle.
+    pub fn spawn(
+        storage: DynStorage,
+        consensus: ConsensusHandle,
+    ) -> (SyncService, SyncEventStream, tokio::task::JoinHandle<()>) {
+        let (command_tx, command_rx) = mpsc::channel(256);
+        let (event_tx, event_rx) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+
+        let engine = SyncingEngine {
+            commands: command_rx,
+            events: event_tx.clone(),
+            import_queue: ImportQueue::new(),
+            storage,
+            consensus,
+        };
+
+        let join_handle = tokio::spawn(engine.run());
+
+        (
+            SyncService {
+                commands: command_tx,
+                events: event_tx,
+            },
+            SyncEventStream(event_rx),
+            join_handle,
+        )
+    }
+
+    async fn run(mut self) {
+        while let Some(command) = self.commands.recv().await {
+            match command {
+                SyncCommand::EnqueueHeader(hash) => self.import_queue.push_header(hash),
+                SyncCommand::EnqueueBlock(block) => {
+                    self.import_queue.push_block(block);
+                    self.drain_import_queue().await;
+                }
+                SyncCommand::SetTargetHeight(height) => self.import_queue.set_target_height(height),
+                SyncCommand::PeerConnected(addr) => {
+                    let _ = self.events.send(SyncEvent::PeerConnected(addr));
+                }
+                SyncCommand::PeerDisconnected(addr) => {
+                    let _ = self.events.send(SyncEvent::PeerDisconnected(addr));
+                }
+            }
+        }
+    }
+
+    /// Verifies and commits every block currently queued through consensus's real commit
+    /// pipeline, publishing `SyncProgress` as it goes and `ChainSynced` once the queue runs dry.
+    /// Backpressure is explicit: the `ImportQueue` itself is unbounded, but while this runs the
+    /// engine reads no commands, so once the bounded `mpsc` command channel fills, further
+    /// `enqueue_block` calls wait on it.
+    async fn drain_import_queue(&mut self) {
+        while let Some(block) = self.import_queue.pop_block() {
+            let height = block.header.height;
+
+            if let Err(e) = self.consensus.commit_block(block).await {
+                log::warn!("failed to verify/commit queued block {}: {}", height, e);
+                continue;
+            }
+
+            let target_height = self.import_queue.target_height().unwrap_or(height);
+            let _ = self.events.send(SyncEvent::SyncProgress {
+                current_height: height,
+                target_height,
+            });
+        }
+
+        if self.import_queue.is_empty() {
+            if let Ok(height) = self.storage.canon().await.map(|canon| canon.block_height as u32) {
+                let _ = self.events.send(SyncEvent::ChainSynced { height });
+            }
+        }
+    }
+}
@@ -3,6 +3,7 @@ mod sync_integration {
     use snarkos_network::{
         message::{types::*, Channel, Message},
         protocol::sync::*,
+        syncing::{light_client::HeaderStore, SyncEvent, SyncingEngine},
         test_data::*,
     };
     use snarkos_objects::BlockHeaderHash;
@@ -27,9 +28,11 @@ mod sync_integration {
 
             // 1. Push hash to sync handler, set syncing to true
 
-            let mut sync_handler = SyncHandler::new(bootnode_address);
+            let (consensus, _consensus_join_handle) = spawn_test_consensus(storage.clone());
+            let (sync_service, _events, _join_handle) = SyncingEngine::spawn(storage.clone(), consensus);
+            let mut sync_handler = SyncHandler::new(bootnode_address, sync_service);
             sync_handler.block_headers.push(block_hash.clone());
-            sync_handler.update_syncing(1);
+            sync_handler.update_syncing(1).await;
 
             // 2. Call increment_sync_handler internally
 
@@ -66,8 +69,10 @@ mod sync_integration {
 
             // 1. Set syncing to true
 
-            let mut sync_handler = SyncHandler::new(bootnode_address);
-            sync_handler.update_syncing(0);
+            let (consensus, _consensus_join_handle) = spawn_test_consensus(storage.clone());
+            let (sync_service, _events, _join_handle) = SyncingEngine::spawn(storage.clone(), consensus);
+            let mut sync_handler = SyncHandler::new(bootnode_address, sync_service);
+            sync_handler.update_syncing(0).await;
 
             // 2. Call increment_sync_handler_internally
             let (tx, rx) = oneshot::channel();
@@ -91,4 +96,68 @@ mod sync_integration {
             kill_storage_async(path);
         }
     }
+
+    mod syncing_engine {
+        use super::*;
+
+        #[tokio::test]
+        #[serial]
+        async fn notifies_peer_connected() {
+            let (storage, path) = initialize_test_blockchain();
+
+            let (consensus, _consensus_join_handle) = spawn_test_consensus(storage.clone());
+            let (service, mut events, _join_handle) = SyncingEngine::spawn(storage, consensus);
+
+            let peer_addr = random_socket_address();
+            service.notify_peer_connected(peer_addr).await.unwrap();
+
+            match events.recv().await.unwrap() {
+                SyncEvent::PeerConnected(addr) => assert_eq!(addr, peer_addr),
+                other => panic!("expected PeerConnected, got {:?}", other),
+            }
+
+            kill_storage_async(path);
+        }
+
+        #[tokio::test]
+        #[serial]
+        async fn independent_subscribers_each_receive_the_same_event() {
+            let (storage, path) = initialize_test_blockchain();
+
+            let (consensus, _consensus_join_handle) = spawn_test_consensus(storage.clone());
+            let (service, mut first, _join_handle) = SyncingEngine::spawn(storage, consensus);
+            let mut second = service.subscribe();
+
+            let peer_addr = random_socket_address();
+            service.notify_peer_connected(peer_addr).await.unwrap();
+
+            for events in [&mut first, &mut second] {
+                match events.recv().await.unwrap() {
+                    SyncEvent::PeerConnected(addr) => assert_eq!(addr, peer_addr),
+                    other => panic!("expected PeerConnected, got {:?}", other),
+                }
+            }
+
+            kill_storage_async(path);
+        }
+    }
+
+    mod light_client {
+        use super::*;
+
+        #[test]
+        fn empty_store_has_no_tip() {
+            let store = HeaderStore::new();
+
+            assert_eq!(store.tip_height(), None);
+            assert_eq!(store.tip_hash(), None);
+        }
+
+        #[test]
+        fn membership_check_fails_for_unsynced_height() {
+            let store = HeaderStore::new();
+
+            assert!(store.verify_commitment_membership(0, &[0u8; 32], &[]).is_err());
+        }
+    }
 }